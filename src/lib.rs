@@ -5,20 +5,153 @@ extern crate input;
 extern crate window;
 
 use core::convert::From;
+use std::collections::VecDeque;
 use input::{ButtonState, Button, Input};
 use input::keyboard::Key;
 use window::{AdvancedWindow, Window};
 
+// Bit flags used by Allegro's `ALLEGRO_KEYMOD_*` modifier mask.
+mod keymod {
+    pub const SHIFT: u32 = 1;
+    pub const CTRL: u32 = 2;
+    pub const ALT: u32 = 4;
+    pub const LWIN: u32 = 8;
+    pub const RWIN: u32 = 16;
+    pub const COMMAND: u32 = 128;
+    pub const SCROLLLOCK: u32 = 256;
+    pub const NUMLOCK: u32 = 512;
+    pub const CAPSLOCK: u32 = 1024;
+}
+
+// Ratio of `pixel_width` to `logical_width`, guarding against a not-yet-sized
+// display rather than dividing by zero.
+fn scale_factor_ratio(logical_width: f64, pixel_width: f64) -> f64 {
+    if logical_width <= 0.0 {
+        return 1.0;
+    }
+    pixel_width / logical_width
+}
+
+// Parses an Allegro ALLEGRO_KEYMOD_* mask into a ModifierState.
+fn modifiers_from_flags(flags: u32) -> ModifierState {
+    ModifierState{
+        shift: flags & keymod::SHIFT != 0,
+        ctrl: flags & keymod::CTRL != 0,
+        alt: flags & keymod::ALT != 0,
+        gui: flags & (keymod::LWIN | keymod::RWIN | keymod::COMMAND) != 0,
+        caps_lock: flags & keymod::CAPSLOCK != 0,
+        num_lock: flags & keymod::NUMLOCK != 0,
+        scroll_lock: flags & keymod::SCROLLLOCK != 0,
+    }
+}
+
+// Merges `scanned` into `existing`, keeping already-known entries at their
+// existing indices and appending only genuinely new ones, so an id assigned
+// by position in the table stays stable when an earlier entry disappears.
+fn merge_ids<T: PartialEq>(existing: Vec<T>, scanned: Vec<T>) -> Vec<T> {
+    let mut merged = existing;
+    for item in scanned {
+        if !merged.iter().any(|m| *m == item) {
+            merged.push(item);
+        }
+    }
+    merged
+}
+
+// Finds `item`'s index in `table`, appending it if it's not already there.
+fn resolve_id<T: PartialEq>(table: &mut Vec<T>, item: T) -> u32 {
+    match table.iter().position(|x| *x == item) {
+        Some(pos) => pos as u32,
+        None => {
+            table.push(item);
+            (table.len() - 1) as u32
+        },
+    }
+}
+
+// Advances the max_fps-paced render accumulator by one update tick of `dt`
+// seconds, returning the new accumulator, the new last-render timestamp, and
+// the ext_dt of each render due this tick (zero or more, since a tick can
+// fall behind and need to catch up).
+fn step_render_cadence(render_accum: f64, last_render: f64, dt: f64, timestamp: f64, max_fps: u64) -> (f64, f64, Vec<f64>) {
+    let mut render_accum = render_accum;
+    let mut last_render = last_render;
+    let mut ext_dts = Vec::new();
+
+    if max_fps > 0 {
+        let render_dt = 1.0 / max_fps as f64;
+        render_accum += dt;
+        while render_accum >= render_dt {
+            render_accum -= render_dt;
+            ext_dts.push(timestamp - last_render);
+            last_render = timestamp;
+        }
+    }
+
+    (render_accum, last_render, ext_dts)
+}
+
+/// Which keyboard modifiers are currently held or toggled on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModifierState {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+/// A cloneable, `Send` handle for emitting custom events from other threads.
+#[derive(Clone)]
+pub struct UserEventHandle(allegro::UserEventSource);
+
+impl UserEventHandle {
+    pub fn emit(&self, payload: u64) {
+        self.0.emit_event(payload);
+    }
+}
+
+// Backs the doc comment's Send claim above at compile time.
+fn _assert_user_event_handle_is_send<T: Send>() {}
+const _: fn() = || _assert_user_event_handle_is_send::<UserEventHandle>();
+
 pub struct AllegroWindow {
     display: allegro::Display,
     event_queue: allegro::EventQueue,
     core: allegro::Core,
+    timer: allegro::Timer,
+    user_events: allegro::UserEventSource,
 
     exit_on_esc: bool,
     title: String,
     event_settings: event_loop::EventSettings,
 
     should_close: bool,
+
+    // Stable table mapping Allegro's opaque joystick handles to Piston ids.
+    joysticks: Vec<allegro::JoystickHandle>,
+
+    // Timestamp of the last timer tick, for deriving dt.
+    last_update: f64,
+    // Seconds accumulated toward the next render event at max_fps.
+    render_accum: f64,
+    // Timestamp of the last render event, for deriving ext_dt.
+    last_render: f64,
+    // Events produced as a side effect of translating another event.
+    pending_events: VecDeque<Input>,
+
+    modifiers: ModifierState,
+
+    // Custom (payload, timestamp) events received via UserEventSource. This
+    // crate's pinned `Input` predates Piston's Loop/Custom split and has no
+    // variant to carry them, so they're queued here for `poll_custom_event`
+    // instead of being returned from wait_event/poll_event.
+    custom_events: VecDeque<(u64, f64)>,
+
+    // Ratio of framebuffer pixels to logical window units.
+    scale_factor: f64,
 }
 
 impl window::BuildFromWindowSettings for AllegroWindow {
@@ -26,28 +159,67 @@ impl window::BuildFromWindowSettings for AllegroWindow {
         let size = settings.get_size();
 
         let core = allegro::Core::init()?;
+
+        let mut flags = allegro::DisplayFlags::WINDOWED;
+        if settings.get_fullscreen() {
+            flags |= allegro::DisplayFlags::FULLSCREEN_WINDOW;
+        }
+        if settings.get_resizable() {
+            flags |= allegro::DisplayFlags::RESIZABLE;
+        }
+        core.set_new_display_flags(flags);
+
         let display = allegro::Display::new(&core, size.width as i32, size.height as i32).map_err(|_| String::from("failed to create display"))?;
         let event_queue = allegro::EventQueue::new(&core).map_err(|_| String::from("failed to create event queue"))?;
 
         core.install_mouse().map_err(|_| "failed to install mouse")?;
         core.install_keyboard().map_err(|_| "failed to install mouse")?;
+        core.install_joystick().map_err(|_| "failed to install joystick")?;
+
+        let event_settings = event_loop::EventSettings::new();
+        let timer = allegro::Timer::new(&core, 1.0 / event_settings.ups as f64).map_err(|_| String::from("failed to create timer"))?;
+        let user_events = allegro::UserEventSource::new();
 
         event_queue.register_event_source(display.get_event_source());
         event_queue.register_event_source(core.get_mouse_event_source().unwrap());
         event_queue.register_event_source(core.get_keyboard_event_source().unwrap());
+        event_queue.register_event_source(core.get_joystick_event_source().unwrap());
+        event_queue.register_event_source(timer.get_event_source());
+        event_queue.register_event_source(user_events.get_event_source());
 
         display.set_window_title(&settings.get_title());
 
+        let joysticks = AllegroWindow::scan_joysticks(&core);
+        let last_update = core.get_time();
+        let scale_factor = AllegroWindow::query_scale_factor(&display);
+
+        timer.start();
+
         Ok(AllegroWindow{
             core,
             display,
             event_queue,
+            timer,
+            user_events,
 
             exit_on_esc: settings.get_exit_on_esc(),
             title: settings.get_title(),
-            event_settings: event_loop::EventSettings::new(),
+            event_settings,
 
             should_close: false,
+
+            joysticks,
+
+            last_update,
+            render_accum: 0.0,
+            last_render: last_update,
+            pending_events: VecDeque::new(),
+
+            modifiers: ModifierState::default(),
+
+            custom_events: VecDeque::new(),
+
+            scale_factor,
         })
     }
 }
@@ -75,44 +247,68 @@ impl Window for AllegroWindow {
     }
 
     fn wait_event(&mut self) -> Input {
-        let event;
+        if let Some(event) = self.pending_events.pop_front() {
+            self.handle_closings(&event);
+            return event;
+        }
         loop {
-            match self.event_queue.wait_for_event() {
-                allegro::Event::NoEvent => (),
-                e => {
-                    event = self.translate_event(e);
-                    break;
-                },
+            let event = match self.event_queue.wait_for_event() {
+                allegro::Event::NoEvent => continue,
+                e => self.translate_event(e),
+            };
+            if let Some(event) = event {
+                self.handle_closings(&event);
+                return event;
             }
         }
-        self.handle_closings(&event);
-        event
     }
 
     fn wait_event_timeout(&mut self, timeout: std::time::Duration) -> Option<Input> {
-        match self.event_queue.wait_for_event_timed(timeout.as_secs() as f64) {
-            allegro::Event::NoEvent => None,
-            e => {
-                let event = self.translate_event(e);
-                self.handle_closings(&event);
-                Some(event)
-            },
+        if let Some(event) = self.pending_events.pop_front() {
+            self.handle_closings(&event);
+            return Some(event);
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.event_queue.wait_for_event_timed(remaining.as_secs_f64()) {
+                allegro::Event::NoEvent => return None,
+                e => match self.translate_event(e) {
+                    Some(event) => {
+                        self.handle_closings(&event);
+                        return Some(event);
+                    },
+                    // Housekeeping-only event; keep waiting out the remaining budget.
+                    None => continue,
+                },
+            }
         }
     }
 
     fn poll_event(&mut self) -> Option<Input> {
-        match self.event_queue.get_next_event() {
-            allegro::Event::NoEvent => None,
-            e => {
-                let event = self.translate_event(e);
-                self.handle_closings(&event);
-                Some(event)
-            },
+        if let Some(event) = self.pending_events.pop_front() {
+            self.handle_closings(&event);
+            return Some(event);
+        }
+        loop {
+            match self.event_queue.get_next_event() {
+                allegro::Event::NoEvent => return None,
+                e => {
+                    if let Some(event) = self.translate_event(e) {
+                        self.handle_closings(&event);
+                        return Some(event);
+                    }
+                },
+            }
         }
     }
 
     fn draw_size(&self) -> window::Size {
-        self.size()
+        let size = self.size();
+        window::Size{
+            width: (size.width as f64 * self.scale_factor).round() as u32,
+            height: (size.height as f64 * self.scale_factor).round() as u32,
+        }
     }
 }
 
@@ -135,19 +331,24 @@ impl AdvancedWindow for AllegroWindow {
     }
 
     fn set_capture_cursor(&mut self, value: bool) {
-        if value {
-            self.core.grab_mouse(&self.display).unwrap();
+        let result = if value {
+            self.core.grab_mouse(&self.display)
         } else {
-            self.core.ungrab_mouse().unwrap();
+            self.core.ungrab_mouse()
+        };
+        if let Err(err) = result {
+            // A failed grab (e.g. an unfocused or unsupported display)
+            // shouldn't take the whole program down with it.
+            eprintln!("allegro_window: failed to set cursor capture: {}", err);
         }
     }
 
     fn show(&mut self) {
-        panic!("not implemented");
+        self.set_visible(true);
     }
 
     fn hide(&mut self) {
-        panic!("not implemented");
+        self.set_visible(false);
     }
 
     fn get_position(&self) -> Option<window::Position> {
@@ -187,11 +388,128 @@ impl event_loop::EventLoop for AllegroWindow {
     }
 
     fn set_event_settings(&mut self, settings: event_loop::EventSettings) {
+        self.timer.set_speed(1.0 / settings.ups as f64);
+        self.render_accum = 0.0;
         self.event_settings = settings;
     }
 }
 
 impl AllegroWindow {
+    /// The keyboard modifiers held as of the most recently processed key event.
+    pub fn modifiers(&self) -> ModifierState {
+        self.modifiers
+    }
+
+    /// A handle other threads can use to push custom events into this window's event loop.
+    pub fn user_event_source(&self) -> UserEventHandle {
+        UserEventHandle(self.user_events.clone())
+    }
+
+    /// Emits a custom event carrying `payload`. It's picked up off the
+    /// queue by `poll_custom_event` rather than returned from
+    /// wait_event/poll_event, since this crate's pinned `Input` has no
+    /// variant to carry it.
+    pub fn emit_user_event(&self, payload: u64) {
+        self.user_events.emit_event(payload);
+    }
+
+    /// Pops the oldest queued custom event as (payload, timestamp), if any.
+    pub fn poll_custom_event(&mut self) -> Option<(u64, f64)> {
+        self.custom_events.pop_front()
+    }
+
+    /// Ratio of framebuffer pixels to logical window units, e.g. 2.0 on a Retina display.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Toggles fullscreen, recreating the display if needed.
+    pub fn set_fullscreen(&mut self, value: bool) {
+        self.set_display_flag(allegro::DisplayFlags::FULLSCREEN_WINDOW, value);
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.display.get_flags().contains(allegro::DisplayFlags::FULLSCREEN_WINDOW)
+    }
+
+    pub fn set_resizable(&mut self, value: bool) {
+        self.set_display_flag(allegro::DisplayFlags::RESIZABLE, value);
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self.display.get_flags().contains(allegro::DisplayFlags::RESIZABLE)
+    }
+
+    /// Shows or hides the title bar and borders.
+    pub fn set_decorated(&mut self, value: bool) {
+        self.set_display_flag(allegro::DisplayFlags::FRAMELESS, !value);
+    }
+
+    pub fn is_decorated(&self) -> bool {
+        !self.display.get_flags().contains(allegro::DisplayFlags::FRAMELESS)
+    }
+
+    /// Shows or hides the mouse cursor, independent of `set_capture_cursor`.
+    pub fn set_cursor_visible(&mut self, value: bool) -> Result<(), String> {
+        if value {
+            self.core.show_mouse_cursor(&self.display).map_err(|_| String::from("failed to show cursor"))
+        } else {
+            self.core.hide_mouse_cursor(&self.display).map_err(|_| String::from("failed to hide cursor"))
+        }
+    }
+
+    // Shows or hides the window by minimizing/restoring it; unlike
+    // set_display_flag, doesn't fall back to recreating the display.
+    fn set_visible(&mut self, value: bool) {
+        let minimized = !value;
+        if !self.display.set_display_flag(allegro::DisplayFlags::MINIMIZED, minimized) {
+            eprintln!(
+                "allegro_window: failed to {} the window; Allegro reported MINIMIZED can't be changed on this display",
+                if value { "show" } else { "hide" },
+            );
+        }
+    }
+
+    // Toggles a display flag, recreating the display if Allegro reports it
+    // can't be changed live. Not used for show/hide; see set_visible.
+    fn set_display_flag(&mut self, flag: allegro::DisplayFlags, value: bool) {
+        if self.display.set_display_flag(flag, value) {
+            return;
+        }
+
+        let width = self.display.get_width();
+        let height = self.display.get_height();
+        let mut new_flags = self.display.get_flags();
+        if value {
+            new_flags |= flag;
+        } else {
+            new_flags -= flag;
+        }
+
+        self.core.set_new_display_flags(new_flags);
+        match allegro::Display::new(&self.core, width, height) {
+            Ok(new_display) => {
+                self.event_queue.unregister_event_source(self.display.get_event_source());
+                self.event_queue.register_event_source(new_display.get_event_source());
+                new_display.set_window_title(&self.title);
+                self.display = new_display;
+                self.scale_factor = AllegroWindow::query_scale_factor(&self.display);
+                // NOTE: mouse grab/cursor-visibility aren't reapplied to the new display.
+            },
+            Err(_) => {
+                // Keep the Core's pending flags matching the still-live display.
+                self.core.set_new_display_flags(self.display.get_flags());
+            },
+        }
+    }
+
+    // Allegro has no dedicated HiDPI query; the backbuffer is sized in
+    // framebuffer pixels while get_width() reports logical units.
+    fn query_scale_factor(display: &allegro::Display) -> f64 {
+        let pixel_width = display.get_backbuffer().get_width() as f64;
+        scale_factor_ratio(display.get_width() as f64, pixel_width)
+    }
+
     fn handle_closings(&mut self, event: &Input) {
         if self.exit_on_esc {
             if let &Input::Button(input::ButtonArgs{state: ButtonState::Press, button: Button::Keyboard(Key::Escape), ..}) = event {
@@ -200,25 +518,70 @@ impl AllegroWindow {
         }
     }
 
-    fn translate_event(&self, event: allegro::Event) -> Input {
+    fn translate_event(&mut self, event: allegro::Event) -> Option<Input> {
         use allegro::Event::*;
-        match event {
+        Some(match event {
             NoEvent => panic!("received no event!"),
             DisplayClose{..} => Input::Close(input::CloseArgs),
-            DisplayResize{width, height, ..} => Input::Resize(width as u32, height as u32),
-            JoystickAxes{..} | JoystickButtonDown{..} | JoystickButtonUp{..} | JoystickConfiguration{..} => panic!("joystick events not supported"),
-            KeyDown{keycode, ..} => Input::Button(input::ButtonArgs{
+            DisplayResize{width, height, ..} => {
+                if let Err(err) = self.display.acknowledge_resize() {
+                    // A dragged corner is an ordinary runtime event, not a
+                    // programmer error; don't take the program down with it.
+                    eprintln!("allegro_window: failed to acknowledge resize: {}", err);
+                }
+                self.scale_factor = AllegroWindow::query_scale_factor(&self.display);
+                Input::Resize(width as u32, height as u32)
+            },
+            JoystickAxes{id, axis, position, ..} => Input::Move(input::Motion::ControllerAxis(input::ControllerAxisArgs{
+                id: self.joystick_id(id),
+                axis: axis as u8,
+                position: position as f64,
+            })),
+            JoystickButtonDown{id, button, ..} => Input::Button(input::ButtonArgs{
                 state: ButtonState::Press,
-                button: Button::Keyboard(self.translate_keycode(keycode)),
+                button: Button::Controller(input::ControllerButton{id: self.joystick_id(id), button: button as u8}),
                 scancode: None,
             }),
-            KeyUp{keycode, ..} => Input::Button(input::ButtonArgs{
+            JoystickButtonUp{id, button, ..} => Input::Button(input::ButtonArgs{
                 state: ButtonState::Release,
-                button: Button::Keyboard(self.translate_keycode(keycode)),
+                button: Button::Controller(input::ControllerButton{id: self.joystick_id(id), button: button as u8}),
                 scancode: None,
             }),
+            JoystickConfiguration{..} => {
+                // A device connected/disconnected. Merge the rescan into the
+                // existing table rather than replacing it wholesale: Allegro
+                // hands back indices in `0..get_num_joysticks()` order, so a
+                // wholesale replacement would shift every handle after a
+                // disconnected one into a lower slot and silently hand its id
+                // to a different, still-connected joystick.
+                self.core.reconfigure_joysticks();
+                let existing = std::mem::take(&mut self.joysticks);
+                self.joysticks = merge_ids(existing, AllegroWindow::scan_joysticks(&self.core));
+                return None;
+            },
+            KeyDown{keycode, modifiers, ..} => {
+                self.update_modifiers(modifiers);
+                Input::Button(input::ButtonArgs{
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(self.translate_keycode(keycode)),
+                    scancode: None,
+                })
+            },
+            KeyUp{keycode, modifiers, ..} => {
+                self.update_modifiers(modifiers);
+                Input::Button(input::ButtonArgs{
+                    state: ButtonState::Release,
+                    button: Button::Keyboard(self.translate_keycode(keycode)),
+                    scancode: None,
+                })
+            },
             KeyChar{unichar, ..} => Input::Text(unichar.to_string()),
-            MouseAxes{dx, dy, ..} => Input::Move(input::Motion::MouseRelative(dx as f64, dy as f64)),
+            MouseAxes{dx, dy, dz, dw, ..} => {
+                if dz != 0 || dw != 0 {
+                    self.pending_events.push_back(Input::Move(input::Motion::MouseScroll(dz as f64, dw as f64)));
+                }
+                Input::Move(input::Motion::MouseRelative(dx as f64, dy as f64))
+            },
             MouseButtonDown{button, ..} => Input::Button(input::ButtonArgs{
                 state: ButtonState::Press,
                 button: Button::Mouse(self.translate_mouse_button(button)),
@@ -232,8 +595,53 @@ impl AllegroWindow {
             MouseWarped{x, y, ..} => Input::Move(input::Motion::MouseCursor(x as f64, y as f64)),
             MouseEnterDisplay{..} => Input::Cursor(true),
             MouseLeaveDisplay{..} => Input::Cursor(false),
-            TimerTick{..} => panic!("timer events not supported"),
-        }
+            UserEvent{data, timestamp, ..} => {
+                self.custom_events.push_back((data, timestamp));
+                return None;
+            },
+            TimerTick{timestamp, ..} => {
+                let dt = timestamp - self.last_update;
+                self.last_update = timestamp;
+
+                let (render_accum, last_render, render_ext_dts) = step_render_cadence(
+                    self.render_accum, self.last_render, dt, timestamp, self.event_settings.max_fps,
+                );
+                self.render_accum = render_accum;
+                self.last_render = last_render;
+
+                if !render_ext_dts.is_empty() {
+                    let size = self.size();
+                    let draw_size = self.draw_size();
+                    for ext_dt in render_ext_dts {
+                        self.pending_events.push_back(Input::Render(input::RenderArgs{
+                            ext_dt,
+                            width: size.width,
+                            height: size.height,
+                            draw_width: draw_size.width,
+                            draw_height: draw_size.height,
+                        }));
+                    }
+                }
+
+                Input::Update(input::UpdateArgs{dt})
+            },
+        })
+    }
+
+    fn scan_joysticks(core: &allegro::Core) -> Vec<allegro::JoystickHandle> {
+        (0..core.get_num_joysticks())
+            .filter_map(|i| core.get_joystick(i))
+            .collect()
+    }
+
+    // Maps an Allegro joystick handle to a stable Piston controller id,
+    // appending it to the table if it's not there yet (e.g. mid-hotplug).
+    fn joystick_id(&mut self, handle: allegro::JoystickHandle) -> u32 {
+        resolve_id(&mut self.joysticks, handle)
+    }
+
+    fn update_modifiers(&mut self, flags: u32) {
+        self.modifiers = modifiers_from_flags(flags);
     }
 
     fn translate_mouse_button(&self, button: u32) -> input::mouse::MouseButton {
@@ -362,3 +770,109 @@ impl AllegroWindow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_ids, modifiers_from_flags, resolve_id, scale_factor_ratio, step_render_cadence, ModifierState, UserEventHandle};
+
+    #[test]
+    fn merge_ids_keeps_existing_ids_stable_when_an_earlier_entry_disconnects() {
+        // "a" (id 0) unplugs; a naive rescan would return just ["b", "c"],
+        // shifting "b" into slot 0 and silently reassigning its id.
+        let existing = vec!["a", "b", "c"];
+        let scanned = vec!["b", "c"];
+        assert_eq!(merge_ids(existing, scanned), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn merge_ids_appends_newly_connected_entries() {
+        let existing = vec!["a", "b"];
+        let scanned = vec!["a", "b", "c"];
+        assert_eq!(merge_ids(existing, scanned), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn resolve_id_reuses_known_entries() {
+        let mut table = vec!["a", "b"];
+        assert_eq!(resolve_id(&mut table, "b"), 1);
+        assert_eq!(table, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn resolve_id_appends_unknown_entries_distinctly() {
+        let mut table = vec!["a"];
+        assert_eq!(resolve_id(&mut table, "b"), 1);
+        assert_eq!(resolve_id(&mut table, "c"), 2);
+        assert_eq!(table, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn step_render_cadence_fires_once_per_tick_at_matching_rates() {
+        // ups=60, max_fps=60: one render per tick, ext_dt equal to dt.
+        let (accum, last_render, ext_dts) = step_render_cadence(0.0, 0.0, 1.0 / 60.0, 1.0 / 60.0, 60);
+        assert_eq!(ext_dts, vec![1.0 / 60.0]);
+        assert_eq!(last_render, 1.0 / 60.0);
+        assert!(accum.abs() < 1e-9);
+    }
+
+    #[test]
+    fn step_render_cadence_catches_up_multiple_renders_in_one_tick() {
+        // A single large tick (e.g. after a stall) owes more than one frame
+        // at max_fps=60; all of them should fire, not just the first.
+        let (accum, last_render, ext_dts) = step_render_cadence(0.0, 0.0, 0.5, 0.5, 60);
+        assert_eq!(ext_dts.len(), 30);
+        assert_eq!(last_render, 0.5);
+        assert!(accum < 1.0 / 60.0);
+    }
+
+    #[test]
+    fn step_render_cadence_reports_true_elapsed_time_not_raw_dt() {
+        // ups=120, max_fps=60: a render is due every other tick, so its
+        // ext_dt should be ~2x the per-tick dt, not the per-tick dt itself.
+        let dt = 1.0 / 120.0;
+        let (accum, last_render, ext_dts) = step_render_cadence(0.0, 0.0, dt, dt, 60);
+        assert!(ext_dts.is_empty());
+
+        let (_, last_render, ext_dts) = step_render_cadence(accum, last_render, dt, 2.0 * dt, 60);
+        assert_eq!(ext_dts.len(), 1);
+        assert!((ext_dts[0] - 2.0 * dt).abs() < 1e-9);
+    }
+
+    #[test]
+    fn step_render_cadence_disabled_when_max_fps_is_zero() {
+        let (accum, last_render, ext_dts) = step_render_cadence(0.0, 0.0, 1.0, 1.0, 0);
+        assert!(ext_dts.is_empty());
+        assert_eq!(accum, 0.0);
+        assert_eq!(last_render, 0.0);
+    }
+
+    #[test]
+    fn scale_factor_ratio_reports_hidpi_scale() {
+        assert_eq!(scale_factor_ratio(800.0, 1600.0), 2.0);
+    }
+
+    #[test]
+    fn scale_factor_ratio_defaults_to_one_when_not_yet_sized() {
+        assert_eq!(scale_factor_ratio(0.0, 1600.0), 1.0);
+    }
+
+    #[test]
+    fn user_event_handle_emits_across_threads() {
+        let handle = UserEventHandle(allegro::UserEventSource::new());
+        std::thread::spawn(move || handle.emit(42)).join().unwrap();
+    }
+
+    #[test]
+    fn modifiers_from_flags_decodes_each_bit() {
+        assert_eq!(modifiers_from_flags(0), ModifierState::default());
+        assert_eq!(modifiers_from_flags(1 | 2 | 4), ModifierState{shift: true, ctrl: true, alt: true, ..Default::default()});
+    }
+
+    #[test]
+    fn modifiers_from_flags_folds_win_and_command_into_gui() {
+        assert_eq!(modifiers_from_flags(8).gui, true);
+        assert_eq!(modifiers_from_flags(16).gui, true);
+        assert_eq!(modifiers_from_flags(128).gui, true);
+        assert_eq!(modifiers_from_flags(0).gui, false);
+    }
+}